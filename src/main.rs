@@ -1,19 +1,28 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::OpenOptions;
 use std::io::{stdout, Write};
 use std::net::IpAddr;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use tokio::fs::{read_link, read_to_string, remove_file, rename, File};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::fs::{metadata, read_link, read_to_string, remove_file, rename, File};
 use eyre::{eyre, ContextCompat, OptionExt, Result, WrapErr};
 use bollard::Docker;
-use bollard::models::ContainerSummary;
+use bollard::models::{ContainerSummary, MountPoint};
 use bollard::query_parameters::{InspectContainerOptions, ListContainersOptions};
+use futures::stream::{self, StreamExt};
 use clap::{Parser, Subcommand};
 use hyper::body::Incoming;
-use hyper::{Request, Response};
+use hyper::{Method, Request, Response, StatusCode};
 use hyper::server::conn::http1;
 use hyper_util::rt::TokioIo;
 use log::{debug, error, info, trace, LevelFilter};
+use rustls_pemfile::{certs, private_key};
+use subtle::ConstantTimeEq;
 use tokio::net::TcpListener;
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
 
 fn get_container_name(container: &ContainerSummary) -> Result<&str> {
     let names = container.names.as_ref().ok_or_eyre("Container has no name")?;
@@ -25,13 +34,18 @@ fn get_container_name(container: &ContainerSummary) -> Result<&str> {
     }
 }
 
-fn write_utf8(output: &mut dyn Write, string: &str) -> std::io::Result<()> {
+fn write_utf8(output: &mut (dyn Write + Send), string: &str) -> std::io::Result<()> {
     output.write(string.as_bytes())?;
     Ok(())
 }
 
-async fn get_device_name(device_maj_min: &str) -> Result<String> {
-    let sys_path = Path::new("/sys/dev/block").join(device_maj_min);
+/// Escapes a string for use as a Prometheus label value, per the exposition format spec.
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+async fn get_device_name(host_root: &Path, device_maj_min: &str) -> Result<String> {
+    let sys_path = host_root.join("sys/dev/block").join(device_maj_min);
 
     let target_path = read_link(sys_path).await?;
 
@@ -42,11 +56,53 @@ async fn get_device_name(device_maj_min: &str) -> Result<String> {
         .to_owned())
 }
 
-async fn process_iostat(container_name: &str, iostat_output: &str, output: &mut dyn Write) -> Result<()> {
+/// Decomposes a raw `st_dev` value into the `major:minor` pair used under `/sys/dev/block`.
+fn major_minor(dev: u64) -> String {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & 0xffff_ff00);
+    format!("{}:{}", major, minor)
+}
+
+async fn process_mounts(host_root: &Path, container_name: &str, mounts: &[MountPoint], output: &mut (dyn Write + Send)) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    for mount in mounts {
+        let Some(source) = mount.source.as_ref() else { continue };
+        let Some(destination) = mount.destination.as_ref() else { continue };
+
+        let source_path = host_root.join(source.trim_start_matches('/'));
+
+        let meta = match metadata(&source_path).await {
+            Ok(meta) => meta,
+            Err(_) => continue,
+        };
+
+        let device_name = match get_device_name(host_root, &major_minor(meta.dev())).await {
+            Ok(device_name) => device_name,
+            Err(_) => continue,
+        };
+
+        let name = mount.name.clone().unwrap_or_default();
+        let typ = mount.typ.clone().map(|typ| typ.to_string()).unwrap_or_default();
+
+        if !seen.insert((device_name.clone(), destination.clone())) {
+            continue;
+        }
+
+        let destination = escape_label_value(destination);
+        let name = escape_label_value(&name);
+
+        write_utf8(output, &format!("docker_mount_info{{container=\"{container_name}\",device=\"{device_name}\",destination=\"{destination}\",name=\"{name}\",type=\"{typ}\"}} 1\n"))?;
+    }
+
+    Ok(())
+}
+
+async fn process_iostat(host_root: &Path, container_name: &str, iostat_output: &str, output: &mut (dyn Write + Send)) -> Result<()> {
     for line in iostat_output.lines() {
         let mut entries = line.split_ascii_whitespace();
 
-        let device_name = get_device_name(&entries.next().ok_or_eyre("Couldn't get device ID")?).await?;
+        let device_name = get_device_name(host_root, &entries.next().ok_or_eyre("Couldn't get device ID")?).await?;
 
         for entry in entries {
             let (key, value) = entry.split_once('=').ok_or_eyre("Failed to split entry")?;
@@ -58,7 +114,7 @@ async fn process_iostat(container_name: &str, iostat_output: &str, output: &mut
     Ok(())
 }
 
-async fn process_iopressure(container_name: &str, iopressure_output: &str, output: &mut dyn Write) -> Result<()> {
+async fn process_iopressure(container_name: &str, iopressure_output: &str, output: &mut (dyn Write + Send)) -> Result<()> {
     for line in iopressure_output.lines() {
         let mut entries = line.split_ascii_whitespace();
 
@@ -74,49 +130,169 @@ async fn process_iopressure(container_name: &str, iopressure_output: &str, outpu
     Ok(())
 }
 
-async fn process_container(docker: &Docker, name: &str, output: &mut dyn Write) -> Result<()> {
+async fn process_container(docker: &Docker, host_root: &Path, name: &str, output: &mut (dyn Write + Send)) -> Result<()> {
     let inspected = docker.inspect_container(name, None::<InspectContainerOptions>).await?;
 
     let state = inspected.state.ok_or_eyre("Error reading container state")?;
 
     let pid = state.pid.ok_or_eyre("Error reading container pid")?;
 
+    if let Some(mounts) = inspected.mounts.as_ref() {
+        process_mounts(host_root, name, mounts, output).await?;
+    }
+
     trace!("Container state PID: {}", pid);
 
-    let cgroup_output = read_to_string(format!("/proc/{}/cgroup", pid)).await
+    let cgroup_output = read_to_string(host_root.join(format!("proc/{}/cgroup", pid))).await
         .wrap_err("Error reading cgroup information")?;
 
-    if !cgroup_output.starts_with("0::/") {
-        return Err(eyre!("Error parsing cgroup. Are you sure you're on cgroup v2?"));
+    let first_line = cgroup_output.lines().next().ok_or_eyre("Empty cgroup information")?;
+
+    let collector: Box<dyn Collector> = if first_line.starts_with("0::") {
+        Box::new(CgroupV2Collector)
+    } else {
+        Box::new(CgroupV1Collector)
+    };
+
+    collector.collect(host_root, name, &cgroup_output, output).await?;
+
+    Ok(())
+}
+
+/// Reads a container's cgroup I/O accounting and reports it in Prometheus format.
+///
+/// Implementations are dispatched on based on the hierarchy format found in `/proc/{pid}/cgroup`,
+/// so the same `docker_iostat_*` metric family can be populated on both cgroup v1 and v2 hosts.
+#[async_trait::async_trait]
+trait Collector: Send {
+    async fn collect(&self, host_root: &Path, container: &str, cgroup_line: &str, output: &mut (dyn Write + Send)) -> Result<()>;
+}
+
+struct CgroupV2Collector;
+
+#[async_trait::async_trait]
+impl Collector for CgroupV2Collector {
+    async fn collect(&self, host_root: &Path, container: &str, cgroup_line: &str, output: &mut (dyn Write + Send)) -> Result<()> {
+        if !cgroup_line.starts_with("0::/") {
+            return Err(eyre!("Error parsing cgroup. Are you sure you're on cgroup v2?"));
+        }
+
+        let cgroup = cgroup_line[4..].trim();
+
+        trace!("Cgroup output: {}", cgroup);
+
+        // `cgroup` is relative to the cgroup root, so it must be re-joined under the prefixed mount.
+        let cgroup_path = host_root.join("sys/fs/cgroup").join(cgroup.trim_start_matches('/'));
+
+        trace!("Full cgroup path: {}", cgroup_path.display());
+
+        let iostat_path = cgroup_path.join("io.stat");
+        let iopressure_path = cgroup_path.join("io.pressure");
+
+        trace!("IOstat path: {}", iostat_path.display());
+
+        let iostat = read_to_string(iostat_path).await?;
+        let iopressure = read_to_string(iopressure_path).await?;
+
+        process_iostat(host_root, container, &iostat, output).await?;
+
+        process_iopressure(container, &iopressure, output).await?;
+
+        Ok(())
     }
+}
+
+struct CgroupV1Collector;
+
+impl CgroupV1Collector {
+    /// Reads a `blkio.throttle.io_service_bytes`/`io_serviced`-shaped file and emits one
+    /// `docker_iostat_{key}` line per device for the operations `key_for_op` maps.
+    async fn process_blkio_file(
+        host_root: &Path,
+        container: &str,
+        path: &Path,
+        key_for_op: impl Fn(&str) -> Option<&'static str>,
+        output: &mut (dyn Write + Send),
+    ) -> Result<()> {
+        let content = read_to_string(path).await?;
+
+        for line in content.lines() {
+            let mut entries = line.split_ascii_whitespace();
+
+            let Some(device_maj_min) = entries.next() else { continue };
+            if !device_maj_min.contains(':') {
+                // The trailing "Total N" line has no device column.
+                continue;
+            }
 
-    let cgroup = cgroup_output[4..].trim();
+            let Some(op) = entries.next() else { continue };
+            let Some(value) = entries.next() else { continue };
 
-    trace!("Cgroup output: {}", cgroup);
+            let Some(key) = key_for_op(op) else { continue };
 
-    let cgroup_path = Path::new("/sys/fs/cgroup").join(cgroup);
+            let device_name = get_device_name(host_root, device_maj_min).await?;
 
-    trace!("Full cgroup path: {}", cgroup_path.display());
+            write_utf8(output, &format!("docker_iostat_{key}{{device=\"{device_name}\",container=\"{container}\"}} {value}\n"))?;
+        }
 
-    let iostat_path = cgroup_path.join("io.stat");
-    let iopressure_path = cgroup_path.join("io.pressure");
+        Ok(())
+    }
+}
 
-    trace!("IOstat path: {}", iostat_path.display());
+#[async_trait::async_trait]
+impl Collector for CgroupV1Collector {
+    async fn collect(&self, host_root: &Path, container: &str, cgroup_line: &str, output: &mut (dyn Write + Send)) -> Result<()> {
+        let blkio_line = cgroup_line.lines()
+            .find(|line| line.split(':').nth(1).is_some_and(|subsystems| subsystems.split(',').any(|s| s == "blkio")))
+            .ok_or_eyre("Couldn't find a blkio cgroup v1 hierarchy")?;
 
-    let iostat = read_to_string(iostat_path).await?;
-    let iopressure = read_to_string(iopressure_path).await?;
+        let path = blkio_line.splitn(3, ':').nth(2).ok_or_eyre("Malformed cgroup line")?;
 
-    process_iostat(name, &iostat, output).await?;
+        trace!("Blkio cgroup path: {}", path);
 
-    process_iopressure(name, &iopressure, output).await?;
+        let cgroup_path = host_root.join("sys/fs/cgroup/blkio").join(path.trim_start_matches('/'));
 
-    Ok(())
+        let key_for_bytes = |op: &str| match op {
+            "Read" => Some("rbytes"),
+            "Write" => Some("wbytes"),
+            _ => None,
+        };
+        let key_for_ios = |op: &str| match op {
+            "Read" => Some("rios"),
+            "Write" => Some("wios"),
+            _ => None,
+        };
+
+        Self::process_blkio_file(host_root, container, &cgroup_path.join("blkio.throttle.io_service_bytes"), key_for_bytes, output).await?;
+        Self::process_blkio_file(host_root, container, &cgroup_path.join("blkio.throttle.io_serviced"), key_for_ios, output).await?;
+
+        Ok(())
+    }
 }
 
-/// Program that uses cgroup v2 to report container IO statistics in a Prometheus format
+fn parse_concurrency(s: &str) -> std::result::Result<usize, String> {
+    let concurrency: usize = s.parse().map_err(|e| format!("{e}"))?;
+
+    if concurrency == 0 {
+        return Err("concurrency must be at least 1".to_owned());
+    }
+
+    Ok(concurrency)
+}
+
+/// Program that uses cgroups to report container IO statistics in a Prometheus format
 #[derive(Parser, Debug)]
 #[command(version, about)]
 struct Cli {
+    /// Prefix prepended to every /proc and /sys path accessed.
+    ///
+    /// Set this when running the reporter itself inside a container, with the host's
+    /// /proc and /sys bind-mounted under e.g. /host.
+    #[arg(long, default_value = "/", env = "DOCKER_IO_REPORTER_HOST_ROOT", global = true)]
+    host_root: PathBuf,
+    /// Maximum number of containers collected concurrently per scrape.
+    #[arg(long, default_value_t = 8, env = "DOCKER_IO_REPORTER_CONCURRENCY", global = true, value_parser = parse_concurrency)]
+    concurrency: usize,
     #[clap(subcommand)]
     subcommand: CliSubcommand,
 }
@@ -133,6 +309,19 @@ enum CliSubcommand {
         /// Port on which to start the server.
         #[arg(short, long, default_value_t = 9100, env = "DOCKER_IO_REPORTER_PORT")]
         port: u16,
+        /// Path to a PEM-encoded TLS certificate.
+        ///
+        /// Must be provided together with `--tls-key` to serve over HTTPS.
+        #[arg(long, env = "DOCKER_IO_REPORTER_TLS_CERT", requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// Path to the PEM-encoded private key matching `--tls-cert`.
+        #[arg(long, env = "DOCKER_IO_REPORTER_TLS_KEY", requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// Bearer token required on every request's `Authorization` header.
+        ///
+        /// By default, the server answers requests without any authentication.
+        #[arg(long, env = "DOCKER_IO_REPORTER_AUTH_TOKEN")]
+        auth_token: Option<String>,
     },
     /// Saves current stats to the file or standard output.
     Save {
@@ -149,45 +338,195 @@ enum CliSubcommand {
     },
 }
 
-async fn save_stats(output: &mut dyn Write) -> Result<()> {
-    let docker = Docker::connect_with_defaults()?;
+/// Tracks how many times collection has failed for each container over the process's lifetime,
+/// so `docker_io_reporter_container_errors_total` behaves like a proper monotonic counter.
+fn record_container_error(container: &str) -> u64 {
+    static COUNTS: OnceLock<Mutex<HashMap<String, u64>>> = OnceLock::new();
+
+    let counts = COUNTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut counts = counts.lock().unwrap();
+    let count = counts.entry(container.to_owned()).or_insert(0);
+    *count += 1;
+    *count
+}
+
+async fn save_stats(output: &mut (dyn Write + Send), host_root: &Path, concurrency: usize) -> Result<()> {
+    let start = Instant::now();
+
+    let docker = Arc::new(Docker::connect_with_defaults()?);
 
     let containers = docker.list_containers(None::<ListContainersOptions>).await?;
 
-    for container in containers {
-        let name = match get_container_name(&container) {
-            Ok(name) => name,
-            Err(_) => continue,
-        };
+    let names: Vec<String> = containers.iter()
+        .filter_map(|container| get_container_name(container).ok().map(|name| name.to_owned()))
+        .collect();
+
+    let mut results: Vec<(String, Result<Vec<u8>>)> = stream::iter(names)
+        .map(|name| {
+            let docker = Arc::clone(&docker);
+            let host_root = host_root.to_owned();
+
+            async move {
+                trace!("Container with name: {}", name);
+
+                let mut buffer = Vec::new();
+                let result = process_container(&docker, &host_root, &name, &mut buffer).await;
+
+                (name, result.map(|_| buffer))
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
 
-        trace!("Container with name: {}", name);
+    // Keep output deterministic and non-interleaved regardless of completion order.
+    results.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-        let result = process_container(&docker, &name, output).await;
+    let mut body = Vec::new();
 
-        if let Err(e) = result {
-            error!("Error processing container: {:?}", e);
+    for (name, result) in results {
+        match result {
+            Ok(buffer) => body.write_all(&buffer)?,
+            Err(e) => {
+                error!("Error processing container {}: {:?}", name, e);
+
+                let count = record_container_error(&name);
+
+                write_utf8(&mut body, &format!("docker_io_reporter_container_errors_total{{container=\"{name}\"}} {count}\n"))?;
+            }
         }
     }
 
+    write_utf8(&mut body, &format!("docker_io_reporter_scrape_duration_seconds {}\n", start.elapsed().as_secs_f64()))?;
+
+    // SAFETY: body only ever receives bytes written by write_utf8 or other buffers built the same way
+    let body = unsafe { String::from_utf8_unchecked(body) };
+
+    write_utf8(output, &render_metrics(&body))?;
+
     output.flush()?;
 
     Ok(())
 }
 
-async fn handle_request(req: Request<Incoming>) -> Result<Response<String>, hyper::Error> {
+/// Returns the `(type, help)` Prometheus metadata for a metric family, or `None` if `name` isn't
+/// one this reporter emits.
+fn metric_family_metadata(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "docker_mount_info" => Some(("gauge", "Static info linking a container mount to its underlying block device.")),
+        "docker_io_reporter_scrape_duration_seconds" => Some(("gauge", "Time taken to complete the most recent scrape, in seconds.")),
+        "docker_io_reporter_container_errors_total" => Some(("counter", "Number of times collection has failed for a container.")),
+        _ if name.starts_with("docker_iostat_") => Some(("counter", "Cumulative I/O statistic reported by the container's cgroup.")),
+        _ if name.starts_with("docker_iopressure_") => Some(("gauge", "I/O pressure stall information for the container's cgroup.")),
+        _ => None,
+    }
+}
+
+/// Groups the raw metric lines produced by `save_stats` by metric family and prepends a
+/// `# HELP`/`# TYPE` pair to each family, once, as Prometheus requires.
+fn render_metrics(body: &str) -> String {
+    let mut order = Vec::new();
+    let mut families: HashMap<&str, Vec<&str>> = HashMap::new();
+
+    for line in body.lines() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let name = line.split(['{', ' ']).next().unwrap_or(line);
+
+        families.entry(name).or_insert_with(|| {
+            order.push(name);
+            Vec::new()
+        }).push(line);
+    }
+
+    let mut rendered = String::with_capacity(body.len() * 2);
+
+    for name in order {
+        if let Some((type_, help)) = metric_family_metadata(name) {
+            rendered.push_str(&format!("# HELP {name} {help}\n"));
+            rendered.push_str(&format!("# TYPE {name} {type_}\n"));
+        }
+
+        for line in &families[name] {
+            rendered.push_str(line);
+            rendered.push('\n');
+        }
+    }
+
+    rendered
+}
+
+fn is_authorized(req: &Request<Incoming>, auth_token: Option<&str>) -> bool {
+    let Some(auth_token) = auth_token else { return true };
+
+    let Some(header) = req.headers().get(hyper::header::AUTHORIZATION) else { return false };
+    let Ok(header) = header.to_str() else { return false };
+    let Some(token) = header.strip_prefix("Bearer ") else { return false };
+
+    // Constant-time comparison so the scrape token can't be recovered via timing side channels.
+    token.as_bytes().ct_eq(auth_token.as_bytes()).into()
+}
+
+async fn handle_request(req: Request<Incoming>, host_root: &Path, concurrency: usize, auth_token: Option<&str>) -> Result<Response<String>, hyper::Error> {
     debug!("Request received: {:?}", req);
 
-    let mut buffer = Vec::with_capacity(2048);
+    if !is_authorized(&req, auth_token) {
+        return Ok(Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .body("Unauthorized".to_owned())
+            .unwrap());
+    }
+
+    match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => {
+            let mut buffer = Vec::with_capacity(2048);
 
-    save_stats(&mut buffer).await.unwrap();
+            save_stats(&mut buffer, host_root, concurrency).await.unwrap();
 
-    // SAFETY: save_stats only writes valid strings to buffer
-    let stats_str = unsafe { String::from_utf8_unchecked(buffer) };
+            // SAFETY: save_stats only writes valid strings to buffer
+            let stats_str = unsafe { String::from_utf8_unchecked(buffer) };
 
-    Ok(Response::builder()
-        .header("Content-Type", "text/plain")
-        .body(stats_str)
-        .unwrap())
+            Ok(Response::builder()
+                .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+                .body(stats_str)
+                .unwrap())
+        }
+        (&Method::GET, "/") => {
+            Ok(Response::builder()
+                .header("Content-Type", "text/html; charset=utf-8")
+                .body("<html><body><a href=\"/metrics\">/metrics</a></body></html>".to_owned())
+                .unwrap())
+        }
+        _ => {
+            Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body("Not Found".to_owned())
+                .unwrap())
+        }
+    }
+}
+
+/// Maximum time allowed for a client to complete the TLS handshake, so a stalled or
+/// malicious client can't tie up a connection slot indefinitely.
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+fn build_tls_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    let cert_chain = certs(&mut std::io::BufReader::new(std::fs::File::open(cert_path)?))
+        .collect::<std::io::Result<Vec<_>>>()
+        .wrap_err("Error reading TLS certificate")?;
+
+    let key = private_key(&mut std::io::BufReader::new(std::fs::File::open(key_path)?))
+        .wrap_err("Error reading TLS private key")?
+        .ok_or_eyre("No private key found in TLS key file")?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)
+        .wrap_err("Error building TLS config")?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
 }
 
 #[tokio::main]
@@ -201,26 +540,54 @@ async fn main() -> Result<()> {
         .init();
 
     match cli.subcommand {
-        CliSubcommand::Host { ip, port } => {
+        CliSubcommand::Host { ip, port, tls_cert, tls_key, auth_token } => {
             let listener = TcpListener::bind((ip, port)).await?;
 
-            info!("Listening at http://{}:{}", ip, port);
+            let tls_acceptor = match (tls_cert, tls_key) {
+                (Some(cert_path), Some(key_path)) => Some(build_tls_acceptor(&cert_path, &key_path)?),
+                _ => None,
+            };
+
+            info!("Listening at http{}://{}:{}", if tls_acceptor.is_some() { "s" } else { "" }, ip, port);
 
             loop {
                 let (socket, addr) = listener.accept().await?;
 
-                let io = TokioIo::new(socket);
-                let service = hyper::service::service_fn(handle_request);
-
-                let result = http1::Builder::new().serve_connection(io, service).await;
-
-                if let Err(err) = result {
-                    error!("Service failed: {}", err);
-                }
+                let host_root = cli.host_root.clone();
+                let concurrency = cli.concurrency;
+                let auth_token = auth_token.clone();
+                let tls_acceptor = tls_acceptor.clone();
+
+                // Spawned so one slow or stalled client (TLS handshake or otherwise)
+                // can't block the accept loop from serving everyone else.
+                tokio::spawn(async move {
+                    let service = hyper::service::service_fn(move |req| {
+                        let host_root = host_root.clone();
+                        let auth_token = auth_token.clone();
+                        async move { handle_request(req, &host_root, concurrency, auth_token.as_deref()).await }
+                    });
+
+                    match &tls_acceptor {
+                        Some(acceptor) => match timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await {
+                            Ok(Ok(stream)) => {
+                                if let Err(err) = http1::Builder::new().serve_connection(TokioIo::new(stream), service).await {
+                                    error!("Service failed: {}", err);
+                                }
+                            }
+                            Ok(Err(err)) => error!("TLS handshake with {} failed: {}", addr, err),
+                            Err(_) => error!("TLS handshake with {} timed out", addr),
+                        },
+                        None => {
+                            if let Err(err) = http1::Builder::new().serve_connection(TokioIo::new(socket), service).await {
+                                error!("Service failed: {}", err);
+                            }
+                        }
+                    }
+                });
             }
         }
         CliSubcommand::Save { path, atomic } => {
-            let output: &mut dyn Write = match path.as_ref() {
+            let output: &mut (dyn Write + Send) = match path.as_ref() {
                 Some(path) => {
                     let path = if atomic {
                         path.with_file_name(format!("{}.atomic", path.file_name().unwrap().to_str().unwrap()))
@@ -235,7 +602,7 @@ async fn main() -> Result<()> {
                 None => &mut stdout(),
             };
 
-            save_stats(output).await?;
+            save_stats(output, &cli.host_root, cli.concurrency).await?;
 
             if atomic {
                 let path = path.unwrap();